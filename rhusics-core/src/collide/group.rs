@@ -0,0 +1,148 @@
+//! Bitmask based collision filtering, similar to the layer/mask pair on Godot's
+//! `CollisionObject2D`, or rapier's interaction groups.
+//!
+//! Shapes carry a `CollisionGroups`; `rhusics_core::physics::resolution::resolve_contact` and
+//! `rhusics_ecs::physics::ContactSolverSystem` each call [`groups_interact`] for every contact to
+//! decide whether to skip it, resolve it normally, or resolve it with one side's inverse mass
+//! forced to zero via [`apply_one_directional_mask`].
+//! `rhusics_ecs::physics::ContinuousCollisionSystem` calls [`groups_interact`] only to skip
+//! ignored pairs.
+//!
+//! Ideally an ignored pair would never reach this point at all - the broad phase
+//! (`BasicCollisionSystem`/`SpatialCollisionSystem`) would consult `CollisionGroups` before
+//! running narrow-phase contact generation, so disjoint groups never pay for a `Contact` that's
+//! just going to be thrown away. This checkout has no broad-phase systems to wire that into, so
+//! filtering is scoped to contact-resolution/solving/CCD time instead, which still skips every
+//! ignored pair correctly but not as cheaply as filtering before narrow phase would.
+
+use cgmath::num_traits::Zero;
+use specs::{Component, VecStorage};
+
+/// A pair of bitmasks controlling which other shapes a shape interacts with.
+///
+/// `membership` is the set of groups this shape belongs to; `interacts_with` is the set of
+/// groups this shape wants to collide with. By default a shape is a member of, and interacts
+/// with, every group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionGroups {
+    membership: u32,
+    interacts_with: u32,
+}
+
+impl Component for CollisionGroups {
+    type Storage = VecStorage<Self>;
+}
+
+impl Default for CollisionGroups {
+    fn default() -> Self {
+        Self {
+            membership: !0,
+            interacts_with: !0,
+        }
+    }
+}
+
+impl CollisionGroups {
+    /// Create a new set of collision groups.
+    pub fn new(membership: u32, interacts_with: u32) -> Self {
+        Self {
+            membership,
+            interacts_with,
+        }
+    }
+
+    /// The groups this shape is a member of.
+    pub fn membership(&self) -> u32 {
+        self.membership
+    }
+
+    /// The groups this shape wants to interact with.
+    pub fn interacts_with(&self) -> u32 {
+        self.interacts_with
+    }
+}
+
+/// Which of the two shapes in a pair a [`GroupInteraction::OneDirectional`] decision refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// The first shape in the pair.
+    A,
+    /// The second shape in the pair.
+    B,
+}
+
+/// The outcome of testing whether two shapes, given their `CollisionGroups`, should interact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupInteraction {
+    /// Neither shape cares about the other: the broad phase should skip this pair entirely.
+    Ignore,
+    /// Both shapes care about each other: resolve the contact as usual.
+    Mutual,
+    /// Only one shape cares about the other. A contact is still generated, but the side named
+    /// here has its inverse mass forced to zero for this pair, so it pushes the other shape out
+    /// without its own linear velocity changing (Godot calls this "one directional" masking).
+    /// Its inverse inertia is not masked, so an off-centre contact can still change its angular
+    /// velocity.
+    OneDirectional(Side),
+}
+
+/// Decide how (or whether) two shapes with the given `CollisionGroups` should interact.
+pub fn groups_interact(a: CollisionGroups, b: CollisionGroups) -> GroupInteraction {
+    let a_cares = a.interacts_with & b.membership != 0;
+    let b_cares = b.interacts_with & a.membership != 0;
+    match (a_cares, b_cares) {
+        (false, false) => GroupInteraction::Ignore,
+        (true, true) => GroupInteraction::Mutual,
+        (true, false) => GroupInteraction::OneDirectional(Side::B),
+        (false, true) => GroupInteraction::OneDirectional(Side::A),
+    }
+}
+
+/// Apply a [`GroupInteraction`] decision to a pair of inverse masses, zeroing out the inverse
+/// mass of whichever side is configured to ignore the other. Used by the resolution phase so
+/// the ignoring body's linear velocity is left unchanged by a one-directional pair, without
+/// changing its `Mass` component. Only the inverse mass is masked, not the inverse inertia.
+pub fn apply_one_directional_mask<S>(
+    interaction: GroupInteraction,
+    a_inverse_mass: S,
+    b_inverse_mass: S,
+) -> (S, S)
+where
+    S: Zero,
+{
+    match interaction {
+        GroupInteraction::OneDirectional(Side::A) => (S::zero(), b_inverse_mass),
+        GroupInteraction::OneDirectional(Side::B) => (a_inverse_mass, S::zero()),
+        _ => (a_inverse_mass, b_inverse_mass),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_groups_interact_mutually() {
+        let a = CollisionGroups::default();
+        let b = CollisionGroups::default();
+        assert_eq!(GroupInteraction::Mutual, groups_interact(a, b));
+    }
+
+    #[test]
+    fn disjoint_groups_are_ignored() {
+        let a = CollisionGroups::new(0b0001, 0b0001);
+        let b = CollisionGroups::new(0b0010, 0b0010);
+        assert_eq!(GroupInteraction::Ignore, groups_interact(a, b));
+    }
+
+    #[test]
+    fn one_directional_mask_forces_ignoring_side_to_zero() {
+        // `a` is in group 1 and only cares about group 1, `b` is in group 1 but cares about
+        // group 2; `b` doesn't listen for `a`, so `b` is the ignoring side.
+        let a = CollisionGroups::new(0b01, 0b01);
+        let b = CollisionGroups::new(0b01, 0b10);
+        let interaction = groups_interact(a, b);
+        assert_eq!(GroupInteraction::OneDirectional(Side::B), interaction);
+        assert_eq!((1.0, 0.0), apply_one_directional_mask(interaction, 1.0, 1.0));
+    }
+}
@@ -0,0 +1,7 @@
+//! Collision detection support types.
+
+pub mod group;
+pub mod one_way;
+
+pub use self::group::{apply_one_directional_mask, CollisionGroups, GroupInteraction, Side};
+pub use self::one_way::{passes_through_platform, OneWayPlatform};
@@ -0,0 +1,72 @@
+//! One-way ("pass-through") platform support, e.g. letting a player jump up through a platform
+//! but land on top of it, as in rapier's `one_way_platforms2` example.
+//!
+//! A shape configured with [`OneWayPlatform`] should have [`passes_through_platform`] consulted
+//! between narrow phase contact generation and contact resolution: if it returns `true` for a
+//! contact, that contact should be dropped before it reaches `resolve_contact`, instead of being
+//! resolved normally.
+
+use cgmath::{InnerSpace, Zero};
+use specs::{Component, VecStorage};
+
+/// Marks a shape as a one-way platform, allowed to be passed through from one side.
+///
+/// Attach to an entity alongside its usual collision components; consulted by
+/// `rhusics_ecs::ContactResolutionSystem`, which drops a contact against this entity instead of
+/// resolving it when [`passes_through_platform`] says the other body is moving through from the
+/// configured side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OneWayPlatform<V> {
+    /// The platform's surface normal. A body moving with this normal (i.e. from underneath, or
+    /// whichever side the platform is configured to ignore) passes through; a body moving
+    /// against it is stopped as usual.
+    pub normal: V,
+}
+
+impl<V> OneWayPlatform<V> {
+    /// Create a new one-way platform configuration with the given normal.
+    pub fn new(normal: V) -> Self {
+        Self { normal }
+    }
+}
+
+impl<V> Component for OneWayPlatform<V>
+where
+    V: Send + Sync + 'static,
+{
+    type Storage = VecStorage<Self>;
+}
+
+/// Decide whether a contact against a one-way platform should be dropped.
+///
+/// `relative_velocity` is the relative velocity at the contact point (body B's velocity minus
+/// body A's, following the same convention as the `rv` used in
+/// [`resolve_contact`](../../physics/resolution/fn.resolve_contact.html)). Returns `true` when
+/// the body is moving through the platform from its configured pass-through side, meaning the
+/// contact should be suppressed rather than resolved.
+pub fn passes_through_platform<V>(relative_velocity: V, platform: &OneWayPlatform<V>) -> bool
+where
+    V: InnerSpace,
+{
+    relative_velocity.dot(platform.normal) > V::Scalar::zero()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector2;
+
+    #[test]
+    fn moving_with_normal_passes_through() {
+        let platform = OneWayPlatform::new(Vector2::new(0., 1.));
+        let rv = Vector2::new(0., 1.);
+        assert!(passes_through_platform(rv, &platform));
+    }
+
+    #[test]
+    fn moving_against_normal_is_stopped() {
+        let platform = OneWayPlatform::new(Vector2::new(0., 1.));
+        let rv = Vector2::new(0., -1.);
+        assert!(!passes_through_platform(rv, &platform));
+    }
+}
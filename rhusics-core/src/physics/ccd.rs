@@ -0,0 +1,91 @@
+//! Continuous collision detection (CCD) via conservative advancement, for pairs flagged
+//! `CollisionMode::Continuous`. Fast or thin bodies can tunnel through each other between
+//! frames when only discrete, end-of-frame poses are tested; conservative advancement instead
+//! finds the first time of impact (TOI) along the frame's sweep, so the resolution phase can
+//! stop the bodies at impact instead of after they've already interpenetrated.
+//!
+//! In `rhusics_ecs`, `ContinuousCollisionSystem` runs this for every reported contact (it does
+//! not check whether either shape uses `CollisionMode::Continuous`), and
+//! `CurrentFrameUpdateSystem` stops the position of a body with a recorded TOI at that point of
+//! the sweep instead of carrying it all the way to `t = 1`.
+
+use cgmath::BaseFloat;
+use cgmath::num_traits::NumCast;
+
+/// Tolerance below which the gap between two shapes is considered touching, and conservative
+/// advancement stops.
+const TOI_TOLERANCE: f32 = 1e-4;
+
+/// Maximum number of conservative advancement iterations to run before giving up and treating
+/// the pair as not colliding within the frame.
+const MAX_ITERATIONS: usize = 20;
+
+/// Find the time of impact, as a fraction `t` of the frame in `[0, 1]`, between two bodies swept
+/// from their start pose to their `NextFrame` pose, using conservative advancement.
+///
+/// `closest_distance(t)` should return the closest distance between the two shapes at the given
+/// point along the sweep, along with the separation direction (from body A to body B) - in
+/// practice this reuses the support functions of `GJK2`/`GJK3` to find the minimum separating
+/// distance between the two (interpolated) poses. `relative_speed_bound(direction)` should
+/// return the maximum closing speed of the pair projected onto that direction, which bounds how
+/// far `t` can safely be advanced without risking stepping past the true time of impact.
+///
+/// Returns `Some(t)` with the first time of impact if the shapes come within `TOI_TOLERANCE` of
+/// touching before `t` would exceed `1`, or `None` if they never get that close during the
+/// sweep.
+pub fn conservative_advancement<S, V, D, R>(
+    mut closest_distance: D,
+    mut relative_speed_bound: R,
+) -> Option<S>
+where
+    S: BaseFloat,
+    D: FnMut(S) -> (S, V),
+    R: FnMut(V) -> S,
+{
+    let tolerance: S = NumCast::from(TOI_TOLERANCE).unwrap();
+    let mut t = S::zero();
+    for _ in 0..MAX_ITERATIONS {
+        let (distance, direction) = closest_distance(t);
+        if distance <= tolerance {
+            return Some(t);
+        }
+        let speed_bound = relative_speed_bound(direction);
+        if speed_bound <= S::zero() {
+            // Not closing along the separation direction any longer; the pair won't meet
+            // during this sweep.
+            return None;
+        }
+        t = t + distance / speed_bound;
+        if t > S::one() {
+            return None;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector2;
+
+    #[test]
+    fn finds_toi_for_closing_bodies() {
+        let initial_gap = 0.5_f32;
+        let closing_speed = 1.0_f32;
+        let toi = conservative_advancement(
+            |t: f32| (initial_gap - t * closing_speed, Vector2::new(1., 0.)),
+            |_direction: Vector2<f32>| closing_speed,
+        );
+        assert!(toi.is_some());
+        assert!((toi.unwrap() - initial_gap / closing_speed).abs() < 1e-3);
+    }
+
+    #[test]
+    fn returns_none_when_never_touching() {
+        let toi = conservative_advancement(
+            |_t: f32| (10.0_f32, Vector2::new(1., 0.)),
+            |_direction: Vector2<f32>| 0.1_f32,
+        );
+        assert!(toi.is_none());
+    }
+}
@@ -0,0 +1,78 @@
+//! Per-body linear/angular velocity damping, so bodies can lose velocity to simulated drag
+//! without relying purely on collision losses (matches the `damping2` scenario in rapier's
+//! testbed).
+
+use cgmath::BaseFloat;
+use specs::{Component, VecStorage};
+
+/// Linear and angular damping coefficients for a single body.
+///
+/// Consumed by `NextFrameSetupSystem` (in `rhusics_ecs`), which scales the velocity it copies
+/// into the next frame by `1 / (1 + damping * dt)` each step. A body
+/// with zero damping (the default) is unaffected and behaves exactly as if it had no `Damping`
+/// component at all.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Damping<S> {
+    /// Linear damping coefficient.
+    pub linear: S,
+    /// Angular damping coefficient.
+    pub angular: S,
+}
+
+impl<S> Default for Damping<S>
+where
+    S: BaseFloat,
+{
+    fn default() -> Self {
+        Self {
+            linear: S::zero(),
+            angular: S::zero(),
+        }
+    }
+}
+
+impl<S> Damping<S>
+where
+    S: BaseFloat,
+{
+    /// Create a new set of damping coefficients.
+    pub fn new(linear: S, angular: S) -> Self {
+        Self { linear, angular }
+    }
+
+    /// The per-step multiplier to apply to the linear velocity, for a step of `dt` seconds.
+    pub fn linear_factor(&self, dt: S) -> S {
+        S::one() / (S::one() + self.linear * dt)
+    }
+
+    /// The per-step multiplier to apply to the angular velocity, for a step of `dt` seconds.
+    pub fn angular_factor(&self, dt: S) -> S {
+        S::one() / (S::one() + self.angular * dt)
+    }
+}
+
+impl<S> Component for Damping<S>
+where
+    S: Send + Sync + 'static,
+{
+    type Storage = VecStorage<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_damping_is_a_no_op() {
+        let damping = Damping::<f32>::default();
+        assert_eq!(1., damping.linear_factor(1. / 60.));
+        assert_eq!(1., damping.angular_factor(1. / 60.));
+    }
+
+    #[test]
+    fn damping_reduces_velocity_factor() {
+        let damping = Damping::new(2.0_f32, 0.5_f32);
+        assert!(damping.linear_factor(1. / 60.) < 1.);
+        assert!(damping.angular_factor(1. / 60.) < 1.);
+    }
+}
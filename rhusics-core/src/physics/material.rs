@@ -0,0 +1,97 @@
+//! Surface properties used during contact resolution.
+
+use cgmath::num_traits::NumCast;
+use specs::{Component, VecStorage};
+
+/// Determines the density, restitution (bounciness), and static/dynamic friction of a body.
+///
+/// Stored using a fixed, high precision representation internally, with generic accessors that
+/// cast to whichever scalar type (`f32` or `f64`) the simulation is running in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    density: f64,
+    restitution: f64,
+    static_friction: f64,
+    dynamic_friction: f64,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            density: 1.,
+            restitution: 1.,
+            static_friction: 0.,
+            dynamic_friction: 0.,
+        }
+    }
+}
+
+impl Material {
+    /// Create a new material with the given density and restitution, and no friction.
+    pub fn new(density: f64, restitution: f64) -> Self {
+        Self {
+            density,
+            restitution,
+            ..Self::default()
+        }
+    }
+
+    /// Create a new material with the given density, restitution, and static/dynamic friction
+    /// coefficients.
+    pub fn new_with_friction(
+        density: f64,
+        restitution: f64,
+        static_friction: f64,
+        dynamic_friction: f64,
+    ) -> Self {
+        Self {
+            density,
+            restitution,
+            static_friction,
+            dynamic_friction,
+        }
+    }
+
+    /// The density of the material.
+    pub fn density<S: NumCast>(&self) -> S {
+        NumCast::from(self.density).unwrap()
+    }
+
+    /// The restitution (bounciness) of the material.
+    pub fn restitution<S: NumCast>(&self) -> S {
+        NumCast::from(self.restitution).unwrap()
+    }
+
+    /// The static friction coefficient of the material, used while the contact is not sliding.
+    pub fn static_friction<S: NumCast>(&self) -> S {
+        NumCast::from(self.static_friction).unwrap()
+    }
+
+    /// The dynamic friction coefficient of the material, used once the contact is sliding.
+    pub fn dynamic_friction<S: NumCast>(&self) -> S {
+        NumCast::from(self.dynamic_friction).unwrap()
+    }
+}
+
+impl Component for Material {
+    type Storage = VecStorage<Self>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_material_has_no_friction() {
+        let material = Material::default();
+        assert_eq!(0., material.static_friction::<f32>());
+        assert_eq!(0., material.dynamic_friction::<f32>());
+    }
+
+    #[test]
+    fn new_with_friction_sets_both_coefficients() {
+        let material = Material::new_with_friction(1., 0.2, 0.6, 0.4);
+        assert_eq!(0.6, material.static_friction::<f32>());
+        assert_eq!(0.4, material.dynamic_friction::<f32>());
+    }
+}
@@ -0,0 +1,9 @@
+//! Physics simulation support: contact resolution and continuous collision detection.
+
+pub mod ccd;
+pub mod damping;
+pub mod material;
+pub mod resolution;
+
+pub use self::damping::Damping;
+pub use self::material::Material;
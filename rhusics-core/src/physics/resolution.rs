@@ -5,6 +5,7 @@ use cgmath::{BaseFloat, EuclideanSpace, InnerSpace, One, Rotation, Transform, Ze
 use cgmath::num_traits::{Float, NumCast};
 use collision::Contact;
 
+use collide::{apply_one_directional_mask, groups_interact, CollisionGroups, GroupInteraction};
 use super::{Inertia, Mass, Material, PartialCrossProduct, Velocity};
 use {BodyPose, NextFrame};
 
@@ -101,11 +102,18 @@ where
     pub mass: &'a Mass<P::Scalar, I>,
     /// Material
     pub material: &'a Material,
+    /// Collision groups, used to decide whether this contact should be resolved at all, and,
+    /// for one-directional pairs, which side has its inverse mass forced to zero (see
+    /// `collide::group::groups_interact`).
+    pub groups: CollisionGroups,
 }
 
 /// Perform contact resolution.
 ///
-/// Will compute any new poses and/or velocities, by doing impulse resolution of the given contact.
+/// Will compute any new poses and/or velocities, by doing impulse resolution of the given
+/// contact. In addition to the normal impulse, a tangential (Coulomb friction) impulse is
+/// applied, using the static and dynamic friction coefficients on `Material`, combined between
+/// the two bodies using the geometric mean.
 ///
 /// ### Parameters:
 ///
@@ -115,7 +123,9 @@ where
 ///
 /// ### Returns
 ///
-/// Tuple of change sets, first change set is for shape A, second change set for shape B.
+/// Tuple of change sets, first change set is for shape A, second change set for shape B. Both
+/// change sets are empty if `a` and `b`'s `CollisionGroups` say the pair should be ignored
+/// entirely (see `collide::group::groups_interact`).
 ///
 /// ### Type parameters:
 ///
@@ -139,14 +149,19 @@ where
     &'a A: Sub<O, Output = A> + Add<O, Output = A>,
     I: Inertia<Orientation = R> + Mul<O, Output = O>,
 {
+    let interaction = groups_interact(a.groups, b.groups);
+    if interaction == GroupInteraction::Ignore {
+        return (SingleChangeSet::default(), SingleChangeSet::default());
+    }
+
     let a_velocity = a.velocity
         .map(|v| v.value.clone())
         .unwrap_or(Velocity::default());
     let b_velocity = b.velocity
         .map(|v| v.value.clone())
         .unwrap_or(Velocity::default());
-    let a_inverse_mass = a.mass.inverse_mass();
-    let b_inverse_mass = b.mass.inverse_mass();
+    let (a_inverse_mass, b_inverse_mass) =
+        apply_one_directional_mask(interaction, a.mass.inverse_mass(), b.mass.inverse_mass());
     let total_inverse_mass = a_inverse_mass + b_inverse_mass;
 
     // Do positional correction, so bodies aren't penetrating as much any longer.
@@ -197,18 +212,47 @@ where
     let j = numerator / (a_inverse_mass + b_inverse_mass + term3 + term4);
     let impulse = contact.normal * j;
 
+    // Compute the tangential (friction) impulse, using the Coulomb friction cone to decide
+    // between static and dynamic friction. No friction is applied when the relative velocity
+    // has no tangential component (e.g. a head on collision).
+    let tangent_rv = rv - contact.normal * velocity_along_normal;
+    let tangent_rv_length = tangent_rv.magnitude();
+    let friction_impulse = if tangent_rv_length > P::Scalar::zero() {
+        let t = tangent_rv / tangent_rv_length;
+
+        let term3_t = t.dot((a_tensor * (r_a.cross(&t))).cross(&r_a));
+        let term4_t = t.dot((b_tensor * (r_b.cross(&t))).cross(&r_b));
+
+        let jt = -rv.dot(t) / (a_inverse_mass + b_inverse_mass + term3_t + term4_t);
+
+        let static_friction =
+            combined_friction(a.material.static_friction(), b.material.static_friction());
+        let dynamic_friction =
+            combined_friction(a.material.dynamic_friction(), b.material.dynamic_friction());
+
+        if jt.abs() < j * static_friction {
+            t * jt
+        } else {
+            t * (-j * dynamic_friction)
+        }
+    } else {
+        P::Diff::zero()
+    };
+
+    let total_impulse = impulse + friction_impulse;
+
     // Compute new velocities based on mass and the computed impulse
     let a_velocity_new = a.velocity.map(|v| NextFrame {
         value: Velocity::new(
-            *v.value.linear() - impulse * a_inverse_mass,
-            v.value.angular() - a_tensor * r_a.cross(&impulse),
+            *v.value.linear() - total_impulse * a_inverse_mass,
+            v.value.angular() - a_tensor * r_a.cross(&total_impulse),
         ),
     });
 
     let b_velocity_new = b.velocity.map(|v| NextFrame {
         value: Velocity::new(
-            *v.value.linear() + impulse * b_inverse_mass,
-            v.value.angular() + b_tensor * r_b.cross(&impulse),
+            *v.value.linear() + total_impulse * b_inverse_mass,
+            v.value.angular() + b_tensor * r_b.cross(&total_impulse),
         ),
     });
 
@@ -218,6 +262,16 @@ where
     (a_set, b_set)
 }
 
+/// Combine the friction coefficients of two materials into a single coefficient to use for a
+/// contact between them, using the geometric mean (matches the combination rule used for
+/// restitution in most box2d-derived solvers).
+fn combined_friction<S>(a: S, b: S) -> S
+where
+    S: BaseFloat,
+{
+    (a * b).sqrt()
+}
+
 /// Do positional correction for colliding bodies.
 ///
 /// Will only do correction for a percentage of the penetration depth, to avoid stability issues.
@@ -308,12 +362,14 @@ mod tests {
                 pose: &left_pose,
                 mass: &mass,
                 material: &material,
+                groups: CollisionGroups::default(),
             },
             ResolveData {
                 velocity: Some(&right_velocity),
                 pose: &right_pose,
                 mass: &mass,
                 material: &material,
+                groups: CollisionGroups::default(),
             },
         );
         assert_eq!(
@@ -364,12 +420,14 @@ mod tests {
                 pose: &left_pose,
                 mass: &mass,
                 material: &material,
+                groups: CollisionGroups::default(),
             },
             ResolveData {
                 velocity: Some(&right_velocity),
                 pose: &right_pose,
                 mass: &mass,
                 material: &material,
+                groups: CollisionGroups::default(),
             },
         );
         assert_eq!(
@@ -396,4 +454,209 @@ mod tests {
             set
         );
     }
+
+    // An oblique contact: both bodies carry a velocity component tangential to the contact
+    // normal, which should be damped by Coulomb friction. The tangent impulse needed to stop the
+    // slide (|jt| = 0.5) is not below `j * static_friction` (0.5 * 0.5 = 0.25), so the friction
+    // cone clamps to dynamic friction instead of fully cancelling the slide.
+    #[test]
+    fn test_resolve_2d_friction_f32() {
+        let mass = Mass::<f32, f32>::new_with_inertia(0.5, 0.);
+        let material = Material::new_with_friction(1., 0., 0.5, 0.3);
+        let left_velocity = NextFrame {
+            value: Velocity::new(Vector2::<f32>::new(2., 1.), 0.),
+        };
+        let left_pose = BodyPose::new(Point2::origin(), Basis2::one());
+        let right_velocity = NextFrame {
+            value: Velocity::new(Vector2::new(0., -1.), 0.),
+        };
+        let right_pose = BodyPose::new(Point2::new(0., 1.), Basis2::one());
+        let contact = ContactEvent::new(
+            (1, 2),
+            Contact::new_impl(CollisionStrategy::FullResolution, Vector2::new(0., 1.), 0.5),
+        );
+        let set = resolve_contact(
+            &contact.contact,
+            ResolveData {
+                velocity: Some(&left_velocity),
+                pose: &left_pose,
+                mass: &mass,
+                material: &material,
+                groups: CollisionGroups::default(),
+            },
+            ResolveData {
+                velocity: Some(&right_velocity),
+                pose: &right_pose,
+                mass: &mass,
+                material: &material,
+                groups: CollisionGroups::default(),
+            },
+        );
+        assert_eq!(
+            (
+                SingleChangeSet {
+                    pose: Some(BodyPose::new(
+                        Point2::new(0., -0.04900000075250864),
+                        Basis2::one()
+                    )),
+                    velocity: Some(NextFrame {
+                        value: Velocity::new(Vector2::new(1.7, 0.), 0.),
+                    }),
+                },
+                SingleChangeSet {
+                    pose: Some(BodyPose::new(
+                        Point2::new(0., 1.0490000007525087),
+                        Basis2::one()
+                    )),
+                    velocity: Some(NextFrame {
+                        value: Velocity::new(Vector2::new(0.3, 0.), 0.),
+                    }),
+                }
+            ),
+            set
+        );
+    }
+
+    #[test]
+    fn test_resolve_2d_friction_f64() {
+        let mass = Mass::<f64, f64>::new_with_inertia(0.5, 0.);
+        let material = Material::new_with_friction(1., 0., 0.5, 0.3);
+        let left_velocity = NextFrame {
+            value: Velocity::new(Vector2::<f64>::new(2., 1.), 0.),
+        };
+        let left_pose = BodyPose::new(Point2::origin(), Basis2::one());
+        let right_velocity = NextFrame {
+            value: Velocity::new(Vector2::new(0., -1.), 0.),
+        };
+        let right_pose = BodyPose::new(Point2::new(0., 1.), Basis2::one());
+        let contact = ContactEvent::new(
+            (1, 2),
+            Contact::new_impl(CollisionStrategy::FullResolution, Vector2::new(0., 1.), 0.5),
+        );
+        let set = resolve_contact(
+            &contact.contact,
+            ResolveData {
+                velocity: Some(&left_velocity),
+                pose: &left_pose,
+                mass: &mass,
+                material: &material,
+                groups: CollisionGroups::default(),
+            },
+            ResolveData {
+                velocity: Some(&right_velocity),
+                pose: &right_pose,
+                mass: &mass,
+                material: &material,
+                groups: CollisionGroups::default(),
+            },
+        );
+        assert_eq!(
+            (
+                SingleChangeSet {
+                    pose: Some(BodyPose::new(
+                        Point2::new(0., -0.04900000075250864),
+                        Basis2::one()
+                    )),
+                    velocity: Some(NextFrame {
+                        value: Velocity::new(Vector2::new(1.7, 0.), 0.),
+                    }),
+                },
+                SingleChangeSet {
+                    pose: Some(BodyPose::new(
+                        Point2::new(0., 1.0490000007525087),
+                        Basis2::one()
+                    )),
+                    velocity: Some(NextFrame {
+                        value: Velocity::new(Vector2::new(0.3, 0.), 0.),
+                    }),
+                }
+            ),
+            set
+        );
+    }
+
+    #[test]
+    fn test_resolve_2d_ignores_disjoint_groups() {
+        // `a` and `b` are in disjoint collision groups, so `resolve_contact` should skip the
+        // pair entirely: no positional correction, no velocity change.
+        let mass = Mass::<f32, f32>::new_with_inertia(0.5, 0.);
+        let material = Material::default();
+        let left_velocity = NextFrame {
+            value: Velocity::new(Vector2::<f32>::new(1., 0.), 0.),
+        };
+        let left_pose = BodyPose::new(Point2::origin(), Basis2::one());
+        let right_velocity = NextFrame {
+            value: Velocity::new(Vector2::new(-2., 0.), 0.),
+        };
+        let right_pose = BodyPose::new(Point2::new(1., 0.), Basis2::one());
+        let contact = ContactEvent::new(
+            (1, 2),
+            Contact::new_impl(CollisionStrategy::FullResolution, Vector2::new(1., 0.), 0.5),
+        );
+        let set = resolve_contact(
+            &contact.contact,
+            ResolveData {
+                velocity: Some(&left_velocity),
+                pose: &left_pose,
+                mass: &mass,
+                material: &material,
+                groups: CollisionGroups::new(0b01, 0b01),
+            },
+            ResolveData {
+                velocity: Some(&right_velocity),
+                pose: &right_pose,
+                mass: &mass,
+                material: &material,
+                groups: CollisionGroups::new(0b10, 0b10),
+            },
+        );
+        assert_eq!(
+            (SingleChangeSet::default(), SingleChangeSet::default()),
+            set
+        );
+    }
+
+    #[test]
+    fn test_resolve_2d_one_directional_group_zeroes_ignoring_side() {
+        // `a` is a member of group 1 but only cares about group 2, `b` is a member of group 1
+        // and cares about group 1: `b` cares about `a` but `a` doesn't care about `b`, so per
+        // `groups_interact` this is a one-directional pair with `a` as the ignoring side. `a`
+        // should act as infinite mass and be left with its original velocity and pose.
+        let mass = Mass::<f32, f32>::new_with_inertia(0.5, 0.);
+        let material = Material::default();
+        let left_velocity = NextFrame {
+            value: Velocity::new(Vector2::<f32>::new(1., 0.), 0.),
+        };
+        let left_pose = BodyPose::new(Point2::origin(), Basis2::one());
+        let right_velocity = NextFrame {
+            value: Velocity::new(Vector2::new(-2., 0.), 0.),
+        };
+        let right_pose = BodyPose::new(Point2::new(1., 0.), Basis2::one());
+        let contact = ContactEvent::new(
+            (1, 2),
+            Contact::new_impl(CollisionStrategy::FullResolution, Vector2::new(1., 0.), 0.5),
+        );
+        let set = resolve_contact(
+            &contact.contact,
+            ResolveData {
+                velocity: Some(&left_velocity),
+                pose: &left_pose,
+                mass: &mass,
+                material: &material,
+                groups: CollisionGroups::new(0b01, 0b10),
+            },
+            ResolveData {
+                velocity: Some(&right_velocity),
+                pose: &right_pose,
+                mass: &mass,
+                material: &material,
+                groups: CollisionGroups::new(0b01, 0b01),
+            },
+        );
+        assert_eq!(
+            BodyPose::new(Point2::origin(), Basis2::one()),
+            set.0.pose.unwrap()
+        );
+        assert_eq!(Vector2::new(1., 0.), *set.0.velocity.unwrap().value.linear());
+    }
 }
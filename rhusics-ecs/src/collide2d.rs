@@ -6,14 +6,14 @@ pub use collision::primitive::{Circle, ConvexPolygon, Particle2, Rectangle};
 pub use core::{CollisionMode, CollisionStrategy};
 pub use core::collide2d::*;
 
-use cgmath::{BaseFloat, Point2, Transform};
+use cgmath::{BaseFloat, Point2, Transform, Vector2};
 use collision::Aabb2;
 use collision::dbvt::{DynamicBoundingVolumeTree, TreeValueWrapped};
 use collision::primitive::Primitive2;
 use specs::{Component, Entity, World};
 
 use collide::{BasicCollisionSystem, SpatialCollisionSystem, SpatialSortingSystem};
-use core::{Collider, ContactEvent};
+use core::{Collider, CollisionGroups, ContactEvent, Damping, Material, OneWayPlatform};
 use resources::WithRhusics;
 
 /// Contact event for 2D
@@ -91,4 +91,8 @@ where
     Y: Collider + Send + Sync + 'static,
 {
     world.register_collision::<Primitive2<S>, Aabb2<S>, T, TreeValueWrapped<Entity, Aabb2<S>>, Y>();
+    world.register::<Damping<S>>();
+    world.register::<Material>();
+    world.register::<CollisionGroups>();
+    world.register::<OneWayPlatform<Vector2<S>>>();
 }
@@ -35,8 +35,9 @@ extern crate specs;
 extern crate serde;
 
 pub use collide::{BasicCollisionSystem, SpatialCollisionSystem, SpatialSortingSystem};
-pub use physics::{ContactResolutionSystem, CurrentFrameUpdateSystem, DeltaTime,
-                  NextFrameSetupSystem, WithLazyRigidBody, WithRigidBody};
+pub use physics::{ContactResolutionSystem, ContactSolverSystem, CurrentFrameUpdateSystem,
+                  DeltaTime, NextFrameSetupSystem, WithLazyRigidBody, WithRigidBody};
+pub use query::{point_query_first, ray_cast_all, ray_cast_first, QueryHit};
 pub use resources::WithRhusics;
 
 pub mod collide2d;
@@ -46,4 +47,5 @@ pub mod physics3d;
 
 mod collide;
 mod physics;
+mod query;
 mod resources;
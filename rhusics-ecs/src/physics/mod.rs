@@ -0,0 +1,488 @@
+//! ECS systems for stepping the physics simulation: next frame setup (with velocity damping),
+//! contact resolution, continuous collision detection, and helpers for attaching rigid body
+//! components to entities.
+
+pub use self::solver::ContactSolverSystem;
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::ops::{Add, Mul, Sub};
+
+use cgmath::{BaseFloat, EuclideanSpace, InnerSpace, Rotation, Zero};
+use shrev::{EventChannel, ReaderId};
+use specs::{Component, Entities, Entity, Fetch, FetchMut, Join, ReadStorage, System,
+            WriteStorage};
+
+use core::{conservative_advancement, groups_interact, passes_through_platform, resolve_contact,
+           BodyPose, CollisionGroups, ContactEvent, Damping, GroupInteraction, Inertia, Mass,
+           Material, NextFrame, OneWayPlatform, PartialCrossProduct, ResolveData, Velocity};
+
+mod solver;
+
+/// Per-entity time of impact found by continuous collision detection this frame, as a fraction
+/// of the frame in `[0, 1]`. Populated by
+/// [`ContinuousCollisionSystem`](struct.ContinuousCollisionSystem.html) for both bodies of any
+/// reported contact it finds a time of impact for; it does not check the shapes'
+/// `CollisionMode`, so this applies to every contact, not only `CollisionMode::Continuous` ones.
+/// Cleared by [`NextFrameSetupSystem`](struct.NextFrameSetupSystem.html) at the start of each
+/// frame, and consulted by [`CurrentFrameUpdateSystem`](struct.CurrentFrameUpdateSystem.html),
+/// which stops the position at the time of impact instead of carrying it all the way to the
+/// interpenetrating end-of-frame pose. See `rhusics_core::physics::ccd::conservative_advancement`.
+#[derive(Debug, Default, Clone)]
+pub struct ContinuousCollisionToi<S> {
+    toi: HashMap<Entity, S>,
+}
+
+impl<S> ContinuousCollisionToi<S>
+where
+    S: BaseFloat,
+{
+    /// Record the time of impact found for `entity` this frame, keeping the earliest one if an
+    /// entity has more than one continuous contact this frame (e.g. sliding towards two thin
+    /// walls at once) - the body must stop at whichever impact comes first, not whichever
+    /// contact happened to be processed last.
+    pub fn insert(&mut self, entity: Entity, toi: S) {
+        let toi = match self.toi.get(&entity) {
+            Some(&existing) => existing.min(toi),
+            None => toi,
+        };
+        self.toi.insert(entity, toi);
+    }
+
+    /// The time of impact recorded for `entity` this frame, if any.
+    pub fn get(&self, entity: Entity) -> Option<S> {
+        self.toi.get(&entity).cloned()
+    }
+
+    /// Clear all recorded times of impact; called at the start of each frame.
+    pub fn clear(&mut self) {
+        self.toi.clear();
+    }
+}
+
+/// Elapsed wall clock time since the last physics step, in seconds.
+///
+/// Used by [`NextFrameSetupSystem`](struct.NextFrameSetupSystem.html) to compute the per-step
+/// `Damping` factors, and by [`ContactSolverSystem`](struct.ContactSolverSystem.html) for its
+/// penetration bias.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct DeltaTime<S> {
+    /// Delta time, in seconds
+    pub delta_seconds: S,
+}
+
+/// Resolve all contacts reported this frame, one at a time.
+///
+/// Reads `ContactEvent`s from the event channel and runs
+/// [`resolve_contact`](../../rhusics_core/physics/resolution/fn.resolve_contact.html) for each,
+/// applying the resulting change set directly to the next frame pose/velocity components. A
+/// contact is dropped before resolution, instead of being resolved, when either body carries a
+/// `OneWayPlatform` that the other is currently passing through.
+///
+/// ### Type parameters:
+///
+/// - `P`: Point type, usually `Point2` or `Point3`
+/// - `R`: Rotational quantity, usually `Basis2` or `Quaternion`
+/// - `I`: Inertia, usually `Scalar` or `Matrix3`
+/// - `A`: Angular velocity, usually `Scalar` or `Vector3`
+/// - `O`: Internal type used for unifying cross products for 2D/3D, usually `Scalar` or `Vector3`
+pub struct ContactResolutionSystem<P, R, I, A, O>
+where
+    P: EuclideanSpace,
+    P::Scalar: BaseFloat,
+    R: Rotation<P>,
+    A: Clone,
+{
+    contact_reader: ReaderId<ContactEvent<Entity, P>>,
+    m: PhantomData<(R, I, A, O)>,
+}
+
+impl<P, R, I, A, O> ContactResolutionSystem<P, R, I, A, O>
+where
+    P: EuclideanSpace,
+    P::Scalar: BaseFloat,
+    R: Rotation<P>,
+    A: Clone,
+{
+    /// Create a new resolution system, reading contacts from the given event channel.
+    pub fn new(contact_reader: ReaderId<ContactEvent<Entity, P>>) -> Self {
+        Self {
+            contact_reader,
+            m: PhantomData,
+        }
+    }
+}
+
+impl<'a, P, R, I, A, O> System<'a> for ContactResolutionSystem<P, R, I, A, O>
+where
+    P: EuclideanSpace + Send + Sync + 'static,
+    P::Scalar: BaseFloat,
+    R: Rotation<P> + Component + Send + Sync + 'static,
+    P::Diff: Debug
+        + Zero
+        + Clone
+        + InnerSpace
+        + PartialCrossProduct<P::Diff, Output = O>
+        + Send
+        + Sync
+        + 'static,
+    O: PartialCrossProduct<P::Diff, Output = P::Diff>,
+    A: PartialCrossProduct<P::Diff, Output = P::Diff>
+        + Clone
+        + Zero
+        + Component
+        + Send
+        + Sync
+        + 'static,
+    &'a A: Sub<O, Output = A> + Add<O, Output = A>,
+    I: Inertia<Orientation = R> + Mul<O, Output = O> + Component + Send + Sync + 'static,
+{
+    type SystemData = (
+        ReadStorage<'a, BodyPose<P, R>>,
+        ReadStorage<'a, Mass<P::Scalar, I>>,
+        ReadStorage<'a, Material>,
+        ReadStorage<'a, CollisionGroups>,
+        ReadStorage<'a, OneWayPlatform<P::Diff>>,
+        WriteStorage<'a, NextFrame<BodyPose<P, R>>>,
+        WriteStorage<'a, NextFrame<Velocity<P::Diff, A>>>,
+        Fetch<'a, EventChannel<ContactEvent<Entity, P>>>,
+    );
+
+    fn run(
+        &mut self,
+        (poses, masses, materials, groups, platforms, mut next_poses, mut next_velocities,
+         contacts): Self::SystemData,
+    ) {
+        for contact in contacts.read(&mut self.contact_reader) {
+            let (a, b) = contact.bodies;
+            let a_velocity = next_velocities.get(a).cloned();
+            let b_velocity = next_velocities.get(b).cloned();
+
+            // Relative linear velocity at the contact, B - A, matching the convention used by
+            // `resolve_contact`/`passes_through_platform`. Only the linear component is used
+            // here, since one-way platforms are ordinarily static and non-rotating.
+            let a_linear = a_velocity
+                .as_ref()
+                .map(|v| *v.value.linear())
+                .unwrap_or(P::Diff::zero());
+            let b_linear = b_velocity
+                .as_ref()
+                .map(|v| *v.value.linear())
+                .unwrap_or(P::Diff::zero());
+            let rv = b_linear - a_linear;
+            // `passes_through_platform` wants the velocity of "the other body" relative to the
+            // platform: that's `rv` when the platform is `a` (the other body is `b`), but `-rv`
+            // when the platform is `b` (the other body is `a`).
+            let blocked_by_platform = platforms
+                .get(a)
+                .map(|platform| passes_through_platform(rv, platform))
+                .unwrap_or(false)
+                || platforms
+                    .get(b)
+                    .map(|platform| passes_through_platform(P::Diff::zero() - rv, platform))
+                    .unwrap_or(false);
+            if blocked_by_platform {
+                continue;
+            }
+
+            let (a_set, b_set) = resolve_contact(
+                &contact.contact,
+                ResolveData {
+                    velocity: a_velocity.as_ref(),
+                    pose: poses.get(a).expect("entity in contact has no BodyPose"),
+                    mass: masses.get(a).expect("entity in contact has no Mass"),
+                    material: materials.get(a).expect("entity in contact has no Material"),
+                    groups: groups.get(a).cloned().unwrap_or_default(),
+                },
+                ResolveData {
+                    velocity: b_velocity.as_ref(),
+                    pose: poses.get(b).expect("entity in contact has no BodyPose"),
+                    mass: masses.get(b).expect("entity in contact has no Mass"),
+                    material: materials.get(b).expect("entity in contact has no Material"),
+                    groups: groups.get(b).cloned().unwrap_or_default(),
+                },
+            );
+            a_set.apply(next_poses.get_mut(a), next_velocities.get_mut(a));
+            b_set.apply(next_poses.get_mut(b), next_velocities.get_mut(b));
+        }
+    }
+}
+
+/// Find the time of impact for every contact reported this frame, via conservative advancement,
+/// and record it in [`ContinuousCollisionToi`] so [`CurrentFrameUpdateSystem`] can stop each body
+/// at impact instead of integrating all the way to the (interpenetrating) end-of-frame pose.
+///
+/// Reads from the same `ContactEvent` channel as [`ContactResolutionSystem`]; every reported
+/// contact is swept from its bodies' current `BodyPose` to their `NextFrame<BodyPose>`, and the
+/// separation along the contact normal is advanced towards zero using
+/// `rhusics_core::physics::ccd::conservative_advancement`. Should run before
+/// `ContactResolutionSystem`/`CurrentFrameUpdateSystem` in the dispatcher, after
+/// `NextFrameSetupSystem` has cleared the previous frame's times of impact and set up the swept
+/// poses.
+///
+/// A contact is skipped before a TOI is swept for it, same as in
+/// [`ContactResolutionSystem`](struct.ContactResolutionSystem.html), when its `CollisionGroups`
+/// say to ignore the pair, or when either body carries a `OneWayPlatform` that the other is
+/// currently passing through.
+///
+/// ### Type parameters:
+///
+/// - `P`: Point type, usually `Point2` or `Point3`
+/// - `R`: Rotational quantity, usually `Basis2` or `Quaternion`
+/// - `A`: Angular velocity, usually `Scalar` or `Vector3`
+pub struct ContinuousCollisionSystem<P, R, A>
+where
+    P: EuclideanSpace,
+    P::Scalar: BaseFloat,
+    R: Rotation<P>,
+    A: Clone,
+{
+    contact_reader: ReaderId<ContactEvent<Entity, P>>,
+    m: PhantomData<(R, A)>,
+}
+
+impl<P, R, A> ContinuousCollisionSystem<P, R, A>
+where
+    P: EuclideanSpace,
+    P::Scalar: BaseFloat,
+    R: Rotation<P>,
+    A: Clone,
+{
+    /// Create a new CCD system, reading contacts from the given event channel.
+    pub fn new(contact_reader: ReaderId<ContactEvent<Entity, P>>) -> Self {
+        Self {
+            contact_reader,
+            m: PhantomData,
+        }
+    }
+}
+
+impl<'a, P, R, A> System<'a> for ContinuousCollisionSystem<P, R, A>
+where
+    P: EuclideanSpace + Send + Sync + 'static,
+    P::Scalar: BaseFloat,
+    P::Diff: InnerSpace + Zero + Clone + Send + Sync + 'static,
+    R: Rotation<P> + Component + Send + Sync + 'static,
+    A: Clone + Component + Send + Sync + 'static,
+{
+    type SystemData = (
+        FetchMut<'a, ContinuousCollisionToi<P::Scalar>>,
+        ReadStorage<'a, BodyPose<P, R>>,
+        ReadStorage<'a, NextFrame<BodyPose<P, R>>>,
+        ReadStorage<'a, NextFrame<Velocity<P::Diff, A>>>,
+        ReadStorage<'a, CollisionGroups>,
+        ReadStorage<'a, OneWayPlatform<P::Diff>>,
+        Fetch<'a, EventChannel<ContactEvent<Entity, P>>>,
+    );
+
+    fn run(
+        &mut self,
+        (mut toi, poses, next_poses, next_velocities, groups, platforms, contacts):
+            Self::SystemData,
+    ) {
+        for contact in contacts.read(&mut self.contact_reader) {
+            let (a, b) = contact.bodies;
+            let (a_pose, b_pose) = match (poses.get(a), poses.get(b)) {
+                (Some(a_pose), Some(b_pose)) => (a_pose, b_pose),
+                _ => continue,
+            };
+            let (a_next, b_next) = match (next_poses.get(a), next_poses.get(b)) {
+                (Some(a_next), Some(b_next)) => (a_next, b_next),
+                _ => continue,
+            };
+
+            let a_groups = groups.get(a).cloned().unwrap_or_default();
+            let b_groups = groups.get(b).cloned().unwrap_or_default();
+            if groups_interact(a_groups, b_groups) == GroupInteraction::Ignore {
+                continue;
+            }
+
+            let a_linear = next_velocities
+                .get(a)
+                .map(|v| *v.value.linear())
+                .unwrap_or(P::Diff::zero());
+            let b_linear = next_velocities
+                .get(b)
+                .map(|v| *v.value.linear())
+                .unwrap_or(P::Diff::zero());
+
+            let rv = b_linear - a_linear;
+            let blocked_by_platform = platforms
+                .get(a)
+                .map(|platform| passes_through_platform(rv, platform))
+                .unwrap_or(false)
+                || platforms
+                    .get(b)
+                    .map(|platform| passes_through_platform(P::Diff::zero() - rv, platform))
+                    .unwrap_or(false);
+            if blocked_by_platform {
+                continue;
+            }
+
+            let a_start = *a_pose.position();
+            let a_end = *a_next.value.position();
+            let b_start = *b_pose.position();
+            let b_end = *b_next.value.position();
+            let normal = contact.contact.normal;
+
+            // Separation of the two bodies along the (fixed) contact normal, linearly
+            // interpolated between their current and swept end-of-frame poses.
+            let closest_distance = |t: P::Scalar| {
+                let a_pos = a_start + (a_end - a_start) * t;
+                let b_pos = b_start + (b_end - b_start) * t;
+                (normal.dot(b_pos - a_pos), normal)
+            };
+            // How fast the bodies are closing along `direction`, treating the relative velocity
+            // as constant over the frame.
+            let relative_speed_bound =
+                |direction: P::Diff| -direction.dot(b_linear - a_linear);
+
+            if let Some(t) = conservative_advancement(closest_distance, relative_speed_bound) {
+                toi.insert(a, t);
+                toi.insert(b, t);
+            }
+        }
+    }
+}
+
+/// Move each body's next frame pose/velocity into its current frame component.
+///
+/// ### Type parameters:
+///
+/// - `P`: Point type, usually `Point2` or `Point3`
+/// - `R`: Rotational quantity, usually `Basis2` or `Quaternion`
+/// - `A`: Angular velocity, usually `Scalar` or `Vector3`
+pub struct CurrentFrameUpdateSystem<P, R, A> {
+    m: PhantomData<(P, R, A)>,
+}
+
+impl<P, R, A> CurrentFrameUpdateSystem<P, R, A> {
+    /// Create a new system instance.
+    pub fn new() -> Self {
+        Self { m: PhantomData }
+    }
+}
+
+impl<'a, P, R, A> System<'a> for CurrentFrameUpdateSystem<P, R, A>
+where
+    P: EuclideanSpace + Send + Sync + 'static,
+    P::Scalar: BaseFloat,
+    P::Diff: Clone + Send + Sync + 'static,
+    R: Rotation<P> + Component + Send + Sync + 'static,
+    A: Clone + Component + Send + Sync + 'static,
+{
+    type SystemData = (
+        Entities<'a>,
+        Fetch<'a, ContinuousCollisionToi<P::Scalar>>,
+        WriteStorage<'a, BodyPose<P, R>>,
+        WriteStorage<'a, NextFrame<BodyPose<P, R>>>,
+        WriteStorage<'a, Velocity<P::Diff, A>>,
+        WriteStorage<'a, NextFrame<Velocity<P::Diff, A>>>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, toi, mut poses, next_poses, mut velocities, next_velocities): Self::SystemData,
+    ) {
+        for (entity, pose, next_pose) in (&*entities, &mut poses, &next_poses).join() {
+            *pose = match toi.get(entity) {
+                // The pair has a time of impact before the end of the frame: stop at the
+                // interpolated position instead of carrying through to the (interpenetrating)
+                // end-of-frame position. The rotation is taken from the end-of-frame pose.
+                Some(t) => BodyPose::new(
+                    *pose.position() + (*next_pose.value.position() - *pose.position()) * t,
+                    next_pose.value.rotation().clone(),
+                ),
+                None => next_pose.value.clone(),
+            };
+        }
+        for (velocity, next_velocity) in (&mut velocities, &next_velocities).join() {
+            *velocity = next_velocity.value.clone();
+        }
+    }
+}
+
+/// Set up the next frame pose and velocity that
+/// [`CurrentFrameUpdateSystem`](struct.CurrentFrameUpdateSystem.html) will pick up once contact
+/// resolution for the current frame has run.
+///
+/// Copies each body's current pose into its next frame pose, and its current velocity into its
+/// next frame velocity, scaled by its `Damping` factors when it has a `Damping` component. No
+/// forces are applied, and the next frame pose is not advanced by the velocity.
+///
+/// ### Type parameters:
+///
+/// - `P`: Point type, usually `Point2` or `Point3`
+/// - `R`: Rotational quantity, usually `Basis2` or `Quaternion`
+/// - `I`: Inertia, usually `Scalar` or `Matrix3`
+/// - `A`: Angular velocity, usually `Scalar` or `Vector3`
+pub struct NextFrameSetupSystem<P, R, I, A> {
+    m: PhantomData<(P, R, I, A)>,
+}
+
+impl<P, R, I, A> NextFrameSetupSystem<P, R, I, A> {
+    /// Create a new system instance.
+    pub fn new() -> Self {
+        Self { m: PhantomData }
+    }
+}
+
+impl<'a, P, R, I, A> System<'a> for NextFrameSetupSystem<P, R, I, A>
+where
+    P: EuclideanSpace + Send + Sync + 'static,
+    P::Scalar: BaseFloat,
+    P::Diff: Clone + Mul<P::Scalar, Output = P::Diff> + Send + Sync + 'static,
+    R: Rotation<P> + Component + Send + Sync + 'static,
+    I: Send + Sync + 'static,
+    A: Clone + Mul<P::Scalar, Output = A> + Component + Send + Sync + 'static,
+{
+    type SystemData = (
+        Fetch<'a, DeltaTime<P::Scalar>>,
+        FetchMut<'a, ContinuousCollisionToi<P::Scalar>>,
+        ReadStorage<'a, BodyPose<P, R>>,
+        WriteStorage<'a, NextFrame<BodyPose<P, R>>>,
+        ReadStorage<'a, Velocity<P::Diff, A>>,
+        WriteStorage<'a, NextFrame<Velocity<P::Diff, A>>>,
+        ReadStorage<'a, Damping<P::Scalar>>,
+    );
+
+    fn run(
+        &mut self,
+        (delta, mut toi, poses, mut next_poses, velocities, mut next_velocities, damping):
+            Self::SystemData,
+    ) {
+        // Times of impact are recomputed by the CCD pass each frame, after this system has set
+        // up the swept poses it needs to test against.
+        toi.clear();
+        for (pose, next_pose) in (&poses, &mut next_poses).join() {
+            next_pose.value = pose.clone();
+        }
+        for (velocity, next_velocity, damping) in
+            (&velocities, &mut next_velocities, damping.maybe()).join()
+        {
+            let mut value = velocity.clone();
+            if let Some(damping) = damping {
+                value = Velocity::new(
+                    *value.linear() * damping.linear_factor(delta.delta_seconds),
+                    value.angular().clone() * damping.angular_factor(delta.delta_seconds),
+                );
+            }
+            next_velocity.value = value;
+        }
+    }
+}
+
+/// Convenience trait for attaching rigid body components (mass, material, velocity) to an
+/// entity when it is created.
+pub trait WithRigidBody {
+    /// Attach the components required to simulate a rigid body.
+    fn with_rigid_body(self) -> Self;
+}
+
+/// Convenience trait for attaching rigid body components lazily, after the entity has already
+/// been built.
+pub trait WithLazyRigidBody {
+    /// Attach the components required to simulate a rigid body, without requiring `&mut World`.
+    fn with_lazy_rigid_body(self) -> Self;
+}
@@ -0,0 +1,457 @@
+//! Iterative, warm-started velocity constraint solver for contacts.
+//!
+//! Unlike [`ContactResolutionSystem`](../struct.ContactResolutionSystem.html), which resolves
+//! each contact independently in a single pass,
+//! [`ContactSolverSystem`](struct.ContactSolverSystem.html) gathers every contact reported in a
+//! frame into a list of velocity constraints and solves them together over several iterations,
+//! as described by Erin Catto's sequential impulse solver.
+//! This converges much better for stacks of overlapping contacts, at the cost of doing more work
+//! per frame.
+//!
+//! Unlike [`resolve_contact`](../../rhusics_core/physics/resolution/fn.resolve_contact.html),
+//! this solver only builds and solves the normal constraint: it does not yet apply a tangential
+//! (Coulomb friction) impulse, so bodies handled by this system will slide frictionlessly along
+//! each other's surfaces regardless of their `Material`'s friction coefficients.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::ops::{Add, Mul, Sub};
+
+use cgmath::{BaseFloat, EuclideanSpace, InnerSpace, One, Rotation, Transform, Zero};
+use shrev::{EventChannel, ReaderId};
+use specs::{Component, Entity, Fetch, Join, ReadStorage, System, WriteStorage};
+
+use core::{apply_one_directional_mask, groups_interact, passes_through_platform, BodyPose,
+           CollisionGroups, ContactEvent, GroupInteraction, Inertia, Mass, Material, NextFrame,
+           OneWayPlatform, PartialCrossProduct, Velocity};
+use physics::DeltaTime;
+
+/// Number of velocity iterations run per frame.
+const SOLVER_ITERATIONS: usize = 10;
+
+/// Baumgarte stabilization factor, used to bleed off a fraction of the penetration depth each
+/// frame as a velocity bias.
+const BAUMGARTE: f32 = 0.2;
+
+/// Penetration allowed before the Baumgarte bias kicks in, to avoid jitter from contacts that
+/// are only barely overlapping.
+const PENETRATION_SLOP: f32 = 0.01;
+
+/// Closing speed below which no restitution bias is applied, to avoid resting contacts
+/// bouncing forever.
+const RESTITUTION_SLOP: f32 = 0.5;
+
+/// A single contact's cached solving data, built fresh each frame but carrying forward the
+/// accumulated normal impulse from the previous frame for warm starting.
+struct VelocityConstraint<P, O>
+where
+    P: EuclideanSpace,
+{
+    a: Entity,
+    b: Entity,
+    r_a: P::Diff,
+    r_b: P::Diff,
+    normal: P::Diff,
+    bias: P::Scalar,
+    restitution: P::Scalar,
+    effective_mass: P::Scalar,
+    a_inverse_mass: P::Scalar,
+    b_inverse_mass: P::Scalar,
+    m: PhantomData<O>,
+}
+
+/// Solve all contacts reported in a frame as a single iterative system, with warm starting.
+///
+/// A contact is dropped before a constraint is built for it, same as in
+/// [`ContactResolutionSystem`](../struct.ContactResolutionSystem.html), when its `CollisionGroups`
+/// say to ignore the pair, or when either body carries a `OneWayPlatform` that the other is
+/// currently passing through; a one-directional group pair has the ignoring side's inverse mass
+/// masked to zero instead, same as `resolve_contact`.
+///
+/// ### Type parameters:
+///
+/// - `P`: Point type, usually `Point2` or `Point3`
+/// - `R`: Rotational quantity, usually `Basis2` or `Quaternion`
+/// - `I`: Inertia, usually `Scalar` or `Matrix3`
+/// - `A`: Angular velocity, usually `Scalar` or `Vector3`
+/// - `O`: Internal type used for unifying cross products for 2D/3D, usually `Scalar` or `Vector3`
+pub struct ContactSolverSystem<P, R, I, A, O>
+where
+    P: EuclideanSpace,
+    P::Scalar: BaseFloat,
+{
+    contact_reader: ReaderId<ContactEvent<Entity, P>>,
+    accumulated_impulse: HashMap<(Entity, Entity), P::Scalar>,
+    m: PhantomData<(R, I, A, O)>,
+}
+
+impl<P, R, I, A, O> ContactSolverSystem<P, R, I, A, O>
+where
+    P: EuclideanSpace,
+    P::Scalar: BaseFloat,
+{
+    /// Create a new solver system, reading contacts from the given event channel.
+    pub fn new(contact_reader: ReaderId<ContactEvent<Entity, P>>) -> Self {
+        Self {
+            contact_reader,
+            accumulated_impulse: HashMap::default(),
+            m: PhantomData,
+        }
+    }
+}
+
+impl<'a, P, R, I, A, O> System<'a> for ContactSolverSystem<P, R, I, A, O>
+where
+    P: EuclideanSpace + Send + Sync + 'static,
+    P::Scalar: BaseFloat,
+    R: Rotation<P> + Component + Send + Sync + 'static,
+    P::Diff: Debug
+        + Zero
+        + Clone
+        + InnerSpace
+        + PartialCrossProduct<P::Diff, Output = O>
+        + Send
+        + Sync
+        + 'static,
+    O: PartialCrossProduct<P::Diff, Output = P::Diff> + Clone,
+    A: PartialCrossProduct<P::Diff, Output = P::Diff>
+        + Clone
+        + Zero
+        + Component
+        + Send
+        + Sync
+        + 'static,
+    &'a A: Sub<O, Output = A> + Add<O, Output = A>,
+    I: Inertia<Orientation = R> + Mul<O, Output = O> + Clone + Component + Send + Sync + 'static,
+{
+    type SystemData = (
+        Fetch<'a, DeltaTime<P::Scalar>>,
+        ReadStorage<'a, BodyPose<P, R>>,
+        ReadStorage<'a, Mass<P::Scalar, I>>,
+        ReadStorage<'a, Material>,
+        ReadStorage<'a, CollisionGroups>,
+        ReadStorage<'a, OneWayPlatform<P::Diff>>,
+        WriteStorage<'a, NextFrame<Velocity<P::Diff, A>>>,
+        Fetch<'a, EventChannel<ContactEvent<Entity, P>>>,
+    );
+
+    fn run(
+        &mut self,
+        (delta, poses, masses, materials, groups, platforms, mut velocities, contacts):
+            Self::SystemData,
+    ) {
+        let dt = delta.delta_seconds;
+        let beta: P::Scalar = cgmath::num_traits::NumCast::from(BAUMGARTE).unwrap();
+        let slop: P::Scalar = cgmath::num_traits::NumCast::from(PENETRATION_SLOP).unwrap();
+        let restitution_slop: P::Scalar =
+            cgmath::num_traits::NumCast::from(RESTITUTION_SLOP).unwrap();
+
+        // Build the list of velocity constraints for this frame, keeping only the previous
+        // frame's accumulated impulse for pairs that are still in contact.
+        let mut next_accumulated = HashMap::default();
+        let mut constraints = Vec::new();
+        for contact in contacts.read(&mut self.contact_reader) {
+            let (a, b) = contact.bodies;
+            let a_pose = match poses.get(a) {
+                Some(pose) => pose,
+                None => continue,
+            };
+            let b_pose = match poses.get(b) {
+                Some(pose) => pose,
+                None => continue,
+            };
+            let a_mass = match masses.get(a) {
+                Some(mass) => mass,
+                None => continue,
+            };
+            let b_mass = match masses.get(b) {
+                Some(mass) => mass,
+                None => continue,
+            };
+
+            let a_groups = groups.get(a).cloned().unwrap_or_default();
+            let b_groups = groups.get(b).cloned().unwrap_or_default();
+            let interaction = groups_interact(a_groups, b_groups);
+            if interaction == GroupInteraction::Ignore {
+                continue;
+            }
+
+            let a_linear = velocities
+                .get(a)
+                .map(|v| *v.value.linear())
+                .unwrap_or(P::Diff::zero());
+            let b_linear = velocities
+                .get(b)
+                .map(|v| *v.value.linear())
+                .unwrap_or(P::Diff::zero());
+            let rv = b_linear - a_linear;
+            let blocked_by_platform = platforms
+                .get(a)
+                .map(|platform| passes_through_platform(rv, platform))
+                .unwrap_or(false)
+                || platforms
+                    .get(b)
+                    .map(|platform| passes_through_platform(P::Diff::zero() - rv, platform))
+                    .unwrap_or(false);
+            if blocked_by_platform {
+                continue;
+            }
+
+            let restitution = match (materials.get(a), materials.get(b)) {
+                (Some(a_material), Some(b_material)) => {
+                    a_material.restitution().min(b_material.restitution())
+                }
+                _ => P::Scalar::zero(),
+            };
+
+            let (a_inverse_mass, b_inverse_mass) = apply_one_directional_mask(
+                interaction,
+                a_mass.inverse_mass(),
+                b_mass.inverse_mass(),
+            );
+            let r_a = contact.contact.contact_point - a_pose.transform_point(P::origin());
+            let r_b = contact.contact.contact_point - b_pose.transform_point(P::origin());
+            let normal = contact.contact.normal;
+
+            let a_tensor = a_mass.world_inverse_inertia(a_pose.rotation());
+            let b_tensor = b_mass.world_inverse_inertia(b_pose.rotation());
+            let k_normal = a_inverse_mass + b_inverse_mass
+                + normal.dot((a_tensor * (r_a.cross(&normal))).cross(&r_a))
+                + normal.dot((b_tensor * (r_b.cross(&normal))).cross(&r_b));
+
+            // Both bodies are effectively immovable along the normal (e.g. two static/kinematic
+            // bodies sharing a contact): skip the constraint instead of dividing by zero and
+            // poisoning the warm-started impulse with NaN.
+            let effective_mass = match effective_mass(k_normal) {
+                Some(effective_mass) => effective_mass,
+                None => continue,
+            };
+
+            let penetration_bias =
+                beta / dt * (contact.contact.penetration_depth - slop).max(P::Scalar::zero());
+
+            // Warm start: re-apply last frame's accumulated impulse for this pair before the
+            // iteration loop runs, so stacked contacts don't have to rebuild it from scratch.
+            let accumulated = *self.accumulated_impulse
+                .get(&(a, b))
+                .unwrap_or(&P::Scalar::zero());
+            if accumulated != P::Scalar::zero() {
+                apply_impulse::<P, R, I, A, O>(
+                    &mut velocities,
+                    a,
+                    b,
+                    &a_tensor,
+                    &b_tensor,
+                    &r_a,
+                    &r_b,
+                    normal * accumulated,
+                    a_inverse_mass,
+                    b_inverse_mass,
+                );
+            }
+
+            constraints.push((
+                VelocityConstraint {
+                    a,
+                    b,
+                    r_a,
+                    r_b,
+                    normal,
+                    bias: penetration_bias,
+                    restitution,
+                    effective_mass,
+                    a_inverse_mass,
+                    b_inverse_mass,
+                    m: PhantomData,
+                },
+                a_tensor,
+                b_tensor,
+                accumulated,
+                restitution_slop,
+            ));
+        }
+
+        for _ in 0..SOLVER_ITERATIONS {
+            for (constraint, a_tensor, b_tensor, accumulated, restitution_slop) in &mut constraints
+            {
+                let a_velocity = velocities
+                    .get(constraint.a)
+                    .map(|v| v.value.clone())
+                    .unwrap_or_default();
+                let b_velocity = velocities
+                    .get(constraint.b)
+                    .map(|v| v.value.clone())
+                    .unwrap_or_default();
+
+                let p_a_dot = *a_velocity.linear() + a_velocity.angular().cross(&constraint.r_a);
+                let p_b_dot = *b_velocity.linear() + b_velocity.angular().cross(&constraint.r_b);
+                let rv = p_b_dot - p_a_dot;
+                let closing_speed = constraint.normal.dot(rv);
+
+                let (new_accumulated, delta_impulse) = solve_velocity_constraint(
+                    closing_speed,
+                    constraint.bias,
+                    constraint.restitution,
+                    *restitution_slop,
+                    constraint.effective_mass,
+                    *accumulated,
+                );
+                *accumulated = new_accumulated;
+
+                apply_impulse::<P, R, I, A, O>(
+                    &mut velocities,
+                    constraint.a,
+                    constraint.b,
+                    a_tensor,
+                    b_tensor,
+                    &constraint.r_a,
+                    &constraint.r_b,
+                    constraint.normal * delta_impulse,
+                    constraint.a_inverse_mass,
+                    constraint.b_inverse_mass,
+                );
+            }
+        }
+
+        next_accumulated.extend(constraints.into_iter().map(|(constraint, _, _, accumulated, _)| {
+            ((constraint.a, constraint.b), accumulated)
+        }));
+        self.accumulated_impulse = next_accumulated;
+    }
+}
+
+/// Apply a linear/angular impulse to both bodies of a pair, in place, following the same sign
+/// convention as
+/// [`resolve_contact`](../../rhusics_core/physics/resolution/fn.resolve_contact.html): the
+/// impulse pushes `a` back along `-impulse` and `b` along `+impulse`.
+fn apply_impulse<'a, P, R, I, A, O>(
+    velocities: &mut WriteStorage<'a, NextFrame<Velocity<P::Diff, A>>>,
+    a: Entity,
+    b: Entity,
+    a_tensor: &I,
+    b_tensor: &I,
+    r_a: &P::Diff,
+    r_b: &P::Diff,
+    impulse: P::Diff,
+    a_inverse_mass: P::Scalar,
+    b_inverse_mass: P::Scalar,
+) where
+    P: EuclideanSpace,
+    P::Scalar: BaseFloat,
+    R: Rotation<P>,
+    P::Diff: Clone + PartialCrossProduct<P::Diff, Output = O>,
+    A: PartialCrossProduct<P::Diff, Output = P::Diff>
+        + Clone
+        + Zero
+        + Component
+        + Send
+        + Sync
+        + 'static,
+    &'a A: Sub<O, Output = A> + Add<O, Output = A>,
+    I: Mul<O, Output = O> + Clone,
+{
+    if let Some(mut a_velocity) = velocities.get_mut(a) {
+        a_velocity.value = Velocity::new(
+            *a_velocity.value.linear() - impulse.clone() * a_inverse_mass,
+            a_velocity.value.angular() - a_tensor.clone() * r_a.cross(&impulse),
+        );
+    }
+    if let Some(mut b_velocity) = velocities.get_mut(b) {
+        b_velocity.value = Velocity::new(
+            *b_velocity.value.linear() + impulse.clone() * b_inverse_mass,
+            b_velocity.value.angular() + b_tensor.clone() * r_b.cross(&impulse),
+        );
+    }
+}
+
+/// The effective mass for a normal constraint, given its `k_normal` denominator (the sum of the
+/// pair's inverse masses plus their angular contributions). Returns `None` when `k_normal` is
+/// zero, meaning both bodies are effectively immovable along the normal, so the constraint
+/// should be skipped rather than dividing by zero.
+fn effective_mass<S>(k_normal: S) -> Option<S>
+where
+    S: BaseFloat,
+{
+    if k_normal == S::zero() {
+        None
+    } else {
+        Some(S::one() / k_normal)
+    }
+}
+
+/// Solve one iteration of a velocity constraint, returning the new accumulated impulse and the
+/// delta to apply this iteration. Factored out of [`ContactSolverSystem::run`] so it can be unit
+/// tested without needing a `specs::World`.
+fn solve_velocity_constraint<S>(
+    closing_speed: S,
+    bias: S,
+    restitution: S,
+    restitution_slop: S,
+    effective_mass: S,
+    accumulated: S,
+) -> (S, S)
+where
+    S: BaseFloat,
+{
+    let restitution_bias = if closing_speed < -restitution_slop {
+        -closing_speed * restitution
+    } else {
+        S::zero()
+    };
+    let lambda = -(closing_speed - bias - restitution_bias) * effective_mass;
+    let new_accumulated = (accumulated + lambda).max(S::zero());
+    (new_accumulated, new_accumulated - accumulated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effective_mass_is_none_for_zero_k_normal() {
+        assert_eq!(None, effective_mass(0.0_f32));
+    }
+
+    #[test]
+    fn effective_mass_is_reciprocal_of_k_normal() {
+        assert_eq!(Some(0.5_f32), effective_mass(2.0_f32));
+    }
+
+    #[test]
+    fn solve_velocity_constraint_converges_closing_speed_to_zero() {
+        // A pair closing at 2 units/s (negative velocity along the normal, following
+        // `resolve_contact`'s convention) with no bias/restitution: after enough iterations the
+        // accumulated impulse should fully cancel the closing speed, same as a single-contact
+        // `resolve_contact` call would.
+        let effective_mass = 0.5_f32;
+        let mut accumulated = 0.0_f32;
+        let mut closing_speed = -2.0_f32;
+        for _ in 0..SOLVER_ITERATIONS {
+            let (new_accumulated, delta_impulse) = solve_velocity_constraint(
+                closing_speed,
+                0.0,
+                0.0,
+                RESTITUTION_SLOP,
+                effective_mass,
+                accumulated,
+            );
+            accumulated = new_accumulated;
+            // Each unit of impulse raises the closing speed by `1 / effective_mass` (i.e. by
+            // `k_normal`), mirroring how `apply_impulse` scales the velocity change on each body
+            // by its (shared, in this example) inverse mass.
+            closing_speed += delta_impulse / effective_mass;
+        }
+        assert!(closing_speed.abs() < 1e-4);
+    }
+
+    #[test]
+    fn solve_velocity_constraint_warm_start_needs_no_further_impulse() {
+        // If the accumulated impulse from a previous frame already cancels the closing speed
+        // exactly, warm starting means this iteration should contribute no further impulse.
+        let (new_accumulated, delta_impulse) =
+            solve_velocity_constraint(0.0_f32, 0.0, 0.0, RESTITUTION_SLOP, 0.5, 4.0);
+        assert_eq!(4.0, new_accumulated);
+        assert_eq!(0.0, delta_impulse);
+    }
+}
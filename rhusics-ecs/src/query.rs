@@ -0,0 +1,140 @@
+//! Spatial queries (ray casts and point queries) over the DBVT used for broad phase/spatial
+//! sorting, similar to heron/rapier's `QueryPipeline`.
+//!
+//! These functions prune candidates using the tree's leaf AABBs, and hand each surviving
+//! candidate to a caller supplied `narrow_phase` closure for the exact intersection test against
+//! that entity's `CollisionShape` primitives - gameplay code can then ask "what entity does this
+//! ray hit first" without iterating every shape in the world. The ray casts additionally visit
+//! candidates front-to-back (nearest AABB entry point along the ray first); `ray_cast_first`
+//! uses this to stop running the (potentially expensive) narrow phase as soon as no remaining
+//! candidate's bound could possibly beat the closest hit found so far. `ray_cast_all` runs the
+//! narrow phase for every candidate and filters the hits by `max_toi` afterwards.
+
+use std::cmp::Ordering;
+
+use cgmath::{BaseFloat, EuclideanSpace, InnerSpace};
+use collision::dbvt::{DynamicBoundingVolumeTree, TreeValueWrapped};
+use collision::{Aabb, Contains, Continuous, Ray};
+use specs::Entity;
+
+/// The result of a successful ray or point query: the entity that was hit, the point of impact,
+/// the surface normal at that point, and - for ray casts - the parametric distance (`toi`)
+/// along the ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QueryHit<P, V, S> {
+    /// The entity that was hit.
+    pub entity: Entity,
+    /// World space point of impact.
+    pub point: P,
+    /// Surface normal at the point of impact.
+    pub normal: V,
+    /// Parametric distance along the ray to the point of impact.
+    pub toi: S,
+}
+
+/// Cast a ray through `tree`, returning the closest hit (if any).
+///
+/// `narrow_phase` is called with the entity behind each AABB the ray intersects, nearest first,
+/// until no remaining AABB could hold a closer hit than the best one found so far. It should
+/// perform the exact ray/primitive test for that entity's shape, returning `None` if the ray
+/// actually misses it.
+pub fn ray_cast_first<S, P, V, A, F>(
+    tree: &DynamicBoundingVolumeTree<TreeValueWrapped<Entity, A>>,
+    ray: &Ray<S, P, V>,
+    mut narrow_phase: F,
+) -> Option<QueryHit<P, V, S>>
+where
+    S: BaseFloat,
+    P: EuclideanSpace<Scalar = S>,
+    P::Diff: InnerSpace,
+    A: Aabb<Scalar = S, Diff = V, Point = P> + Continuous<Ray<S, P, V>, Result = P>,
+    F: FnMut(Entity, &Ray<S, P, V>) -> Option<QueryHit<P, V, S>>,
+{
+    let mut closest: Option<QueryHit<P, V, S>> = None;
+    for (entity, entry_distance) in front_to_back_candidates(tree, ray) {
+        // Candidates are sorted by entry distance, so once the closest bound so far can't beat
+        // the best hit already found, none of the remaining candidates can either.
+        if closest.as_ref().map(|hit| entry_distance >= hit.toi).unwrap_or(false) {
+            break;
+        }
+        if let Some(hit) = narrow_phase(entity, ray) {
+            if closest.as_ref().map(|c| hit.toi < c.toi).unwrap_or(true) {
+                closest = Some(hit);
+            }
+        }
+    }
+    closest
+}
+
+/// Cast a ray through `tree`, returning every hit within `max_toi`, nearest first.
+pub fn ray_cast_all<S, P, V, A, F>(
+    tree: &DynamicBoundingVolumeTree<TreeValueWrapped<Entity, A>>,
+    ray: &Ray<S, P, V>,
+    max_toi: S,
+    mut narrow_phase: F,
+) -> Vec<QueryHit<P, V, S>>
+where
+    S: BaseFloat,
+    P: EuclideanSpace<Scalar = S>,
+    P::Diff: InnerSpace,
+    A: Aabb<Scalar = S, Diff = V, Point = P> + Continuous<Ray<S, P, V>, Result = P>,
+    F: FnMut(Entity, &Ray<S, P, V>) -> Option<QueryHit<P, V, S>>,
+{
+    let mut hits: Vec<_> = front_to_back_candidates(tree, ray)
+        .into_iter()
+        .filter_map(|(entity, _)| narrow_phase(entity, ray))
+        .filter(|hit| hit.toi <= max_toi)
+        .collect();
+    hits.sort_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap_or(Ordering::Equal));
+    hits
+}
+
+/// Every leaf in `tree` whose AABB the ray intersects, paired with its entry distance along the
+/// ray and sorted nearest-first, so callers can visit (and prune) candidates front-to-back
+/// instead of in arbitrary tree order.
+///
+/// The entry distance is the ray's actual intersection point with the AABB (its near face, found
+/// by `Continuous`), not the AABB's center projected onto the ray - a large or ray-aligned AABB
+/// can have its center far down-range while its near face is much closer to the ray origin, so
+/// the center projection isn't a valid lower bound for pruning.
+fn front_to_back_candidates<S, P, V, A>(
+    tree: &DynamicBoundingVolumeTree<TreeValueWrapped<Entity, A>>,
+    ray: &Ray<S, P, V>,
+) -> Vec<(Entity, S)>
+where
+    S: BaseFloat,
+    P: EuclideanSpace<Scalar = S>,
+    P::Diff: InnerSpace,
+    A: Aabb<Scalar = S, Diff = V, Point = P> + Continuous<Ray<S, P, V>, Result = P>,
+{
+    let mut candidates: Vec<_> = tree.values()
+        .iter()
+        .filter_map(|&(ref value, ref bound)| {
+            bound.intersection(ray).map(|point| {
+                let entry_distance = (point - ray.origin).dot(ray.direction);
+                (value.value, entry_distance)
+            })
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+    candidates
+}
+
+/// Test `point` against every leaf in `tree` whose AABB contains it, returning the first entity
+/// (if any) whose exact shape also contains the point.
+pub fn point_query_first<S, P, A, F>(
+    tree: &DynamicBoundingVolumeTree<TreeValueWrapped<Entity, A>>,
+    point: P,
+    mut narrow_phase: F,
+) -> Option<Entity>
+where
+    S: BaseFloat,
+    P: EuclideanSpace<Scalar = S>,
+    A: Aabb<Scalar = S, Point = P> + Contains<P>,
+    F: FnMut(Entity, P) -> bool,
+{
+    tree.values()
+        .iter()
+        .find(|&&(ref value, ref bound)| bound.contains(&point) && narrow_phase(value.value, point))
+        .map(|&(ref value, _)| value.value)
+}